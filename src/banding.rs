@@ -0,0 +1,62 @@
+// Picks the (bands, rows) factorization of hash_count whose LSH S-curve inflection point,
+// (1/bands)^(1/rows), is closest to target_threshold.
+pub fn tune_banding(hash_count: usize, target_threshold: f32) -> (usize, usize) {
+    tune_banding_with_ceiling(hash_count, target_threshold, None)
+}
+
+// false_positive_ceiling, if given, is (low_similarity, max_probability): factorizations whose
+// collision probability at low_similarity exceeds max_probability are discarded first.
+pub fn tune_banding_with_ceiling(
+    hash_count: usize,
+    target_threshold: f32,
+    false_positive_ceiling: Option<(f32, f32)>,
+) -> (usize, usize) {
+    factor_pairs(hash_count)
+        .into_iter()
+        .filter(|&(bands, rows)| match false_positive_ceiling {
+            Some((low_similarity, max_probability)) => {
+                collision_probability(bands, rows, low_similarity) <= max_probability
+            }
+            None => true,
+        })
+        .min_by(|&(bands_a, rows_a), &(bands_b, rows_b)| {
+            let diff_a = (approx_threshold(bands_a, rows_a) - target_threshold).abs();
+            let diff_b = (approx_threshold(bands_b, rows_b) - target_threshold).abs();
+            diff_a.partial_cmp(&diff_b).unwrap()
+        })
+        .expect("hash_count must have at least one (bands, rows) factorization")
+}
+
+// Every (bands, rows) pair with bands * rows == hash_count.
+fn factor_pairs(hash_count: usize) -> Vec<(usize, usize)> {
+    (1..=hash_count)
+        .filter(|rows| hash_count.is_multiple_of(*rows))
+        .map(|rows| (hash_count / rows, rows))
+        .collect()
+}
+
+fn approx_threshold(bands: usize, rows: usize) -> f32 {
+    (1.0 / bands as f32).powf(1.0 / rows as f32)
+}
+
+fn collision_probability(bands: usize, rows: usize, similarity: f32) -> f32 {
+    1.0 - (1.0 - similarity.powf(rows as f32)).powf(bands as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tune_banding_picks_a_valid_factorization() {
+        let (bands, rows) = tune_banding(100, 0.8);
+        assert_eq!(bands * rows, 100);
+    }
+
+    #[test]
+    fn tune_banding_with_ceiling_excludes_factorizations_above_the_false_positive_rate() {
+        let (bands, rows) = tune_banding_with_ceiling(100, 0.8, Some((0.1, 0.01)));
+        assert_eq!(bands * rows, 100);
+        assert!(collision_probability(bands, rows, 0.1) <= 0.01);
+    }
+}
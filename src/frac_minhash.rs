@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+// FracMinHash ("scaled MinHash"): keeps every hash h <= u64::MAX / scale, so sketch size tracks
+// shingle cardinality instead of being capped at HASH_COUNT, which is what lets it estimate
+// containment as well as Jaccard.
+#[derive(Serialize, Deserialize)]
+pub struct FracMinHash {
+    scale: u64,
+}
+
+impl FracMinHash {
+    pub fn new(scale: u64) -> Self {
+        FracMinHash { scale }
+    }
+
+    // Retains the hashes at or below u64::MAX / scale.
+    pub fn sketch(&self, shingles: &HashSet<u64>) -> HashSet<u64> {
+        let threshold = u64::MAX / self.scale;
+        shingles.iter().copied().filter(|&h| h <= threshold).collect()
+    }
+}
+
+// |A ∩ B| / |A ∪ B| over the retained hashes.
+pub fn jaccard_estimate(sketch_a: &HashSet<u64>, sketch_b: &HashSet<u64>) -> f32 {
+    let intersection_cardinality = sketch_a.intersection(sketch_b).count();
+    let union_cardinality = sketch_a.len() + sketch_b.len() - intersection_cardinality;
+    intersection_cardinality as f32 / union_cardinality as f32
+}
+
+// C(A, B) = |A ∩ B| / |A|: stays high for a short document fully contained in a much longer one.
+pub fn containment_estimate(sketch_a: &HashSet<u64>, sketch_b: &HashSet<u64>) -> f32 {
+    if sketch_a.is_empty() {
+        return 0.0;
+    }
+    let intersection_cardinality = sketch_a.intersection(sketch_b).count();
+    intersection_cardinality as f32 / sketch_a.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn containment_is_high_when_a_is_a_subset_of_b() {
+        let a: HashSet<u64> = [1, 2, 3].into_iter().collect();
+        let b: HashSet<u64> = [1, 2, 3, 4, 5, 6, 7, 8].into_iter().collect();
+        assert_eq!(containment_estimate(&a, &b), 1.0);
+        assert!(jaccard_estimate(&a, &b) < containment_estimate(&a, &b));
+    }
+
+    #[test]
+    fn sketch_retains_only_hashes_at_or_below_threshold() {
+        let hasher = FracMinHash::new(2);
+        let shingles: HashSet<u64> = [0, u64::MAX / 2, u64::MAX].into_iter().collect();
+        let sketch = hasher.sketch(&shingles);
+        assert!(sketch.contains(&0));
+        assert!(sketch.contains(&(u64::MAX / 2)));
+        assert!(!sketch.contains(&u64::MAX));
+    }
+}
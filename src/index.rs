@@ -0,0 +1,362 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use rayon::prelude::*;
+use roaring::RoaringBitmap;
+use serde::{Deserialize, Serialize};
+
+use crate::banding::tune_banding;
+use crate::frac_minhash::FracMinHash;
+use crate::minhash::MinHasher;
+use crate::shingles::{document_shingles, ShingleMode};
+
+/// A document's band signature: `(bucket_index, band_hash)` per band, as returned by
+/// `MinHasher::chunked_min_hash`.
+type Signature = Vec<(usize, u64)>;
+
+/// A document's id, text, shingles and band signature, computed once and threaded through to
+/// `insert_signature` so `add_documents` can do this work with `par_iter`. The FracMinHash sketch
+/// is taken from the shingles later, inside `insert_signature`.
+type PreparedDocument = (usize, String, HashSet<u64>, Signature);
+
+/// Permutation/shingle parameters an `LshIndex` was built with, kept alongside the index so that
+/// a reloaded index keeps banding documents the same way it did before being saved.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct LshIndexConfig {
+    pub hash_count: usize,
+    pub band_size: usize,
+    pub shingle_mode: ShingleMode,
+    pub frac_scale: u64,
+}
+
+impl Default for LshIndexConfig {
+    fn default() -> Self {
+        LshIndexConfig {
+            hash_count: crate::HASH_COUNT,
+            band_size: crate::BAND_SIZE,
+            shingle_mode: ShingleMode::Fixed,
+            frac_scale: crate::FRAC_MIN_HASH_SCALE,
+        }
+    }
+}
+
+impl LshIndexConfig {
+    /// Builds a config whose `band_size` (rows per band) and implied band count are chosen by
+    /// `banding::tune_banding` to retrieve pairs near `target_threshold` Jaccard similarity,
+    /// rather than using the fixed `BAND_SIZE` constant.
+    pub fn for_threshold(
+        hash_count: usize,
+        target_threshold: f32,
+        shingle_mode: ShingleMode,
+        frac_scale: u64,
+    ) -> Self {
+        let (_bands, rows) = tune_banding(hash_count, target_threshold);
+        LshIndexConfig {
+            hash_count,
+            band_size: rows,
+            shingle_mode,
+            frac_scale,
+        }
+    }
+}
+
+/// A persistable LSH index: the band posting lists plus the permutation family and shingle
+/// config they were built with. Documents can be added and removed incrementally instead of
+/// requiring a one-shot rebuild of the whole corpus, and the whole structure can
+/// be serialized to disk so a large corpus only has to be min-hashed once.
+///
+/// Posting lists are roaring bitmaps rather than `Vec<usize>`: popular buckets in a near-duplicate
+/// corpus hold huge id sets, and roaring compresses those while still letting `search` gather
+/// candidates as a sequence of cheap bitmap unions instead of hashing every id into a `HashSet`.
+#[derive(Serialize, Deserialize)]
+pub struct LshIndex {
+    config: LshIndexConfig,
+    hasher: MinHasher,
+    frac_hasher: FracMinHash,
+    buckets: Vec<HashMap<u64, RoaringBitmap>>,
+    // Inverted index from a retained FracMinHash hash to the documents whose sketch retains it.
+    // Containment only requires a query's retained hashes to be a subset of a candidate's, so a
+    // single shared hash is enough to surface that candidate -- unlike `buckets`, which needs
+    // every row in some band to collide, and all but misses length-skewed pairs as a result.
+    frac_postings: HashMap<u64, RoaringBitmap>,
+    documents: HashMap<usize, String>,
+    // Each document's band signature, kept so `remove_document` can find exactly the postings
+    // it needs to touch instead of scanning every bucket.
+    signatures: HashMap<usize, Signature>,
+    // Each document's retained FracMinHash hashes, kept so `remove_document` can clean up
+    // `frac_postings` the same way it cleans up `buckets`.
+    frac_sketches: HashMap<usize, HashSet<u64>>,
+}
+
+impl LshIndex {
+    pub fn new(config: LshIndexConfig) -> Self {
+        assert_eq!(
+            config.hash_count % config.band_size,
+            0,
+            "hash_count ({}) must be a multiple of band_size ({}), or chunked_min_hash \
+             produces a partial trailing band that `buckets` has no slot for",
+            config.hash_count,
+            config.band_size
+        );
+        let hasher = MinHasher::new(config.hash_count);
+        // Bands are sized off the hasher's actual signature length rather than a second,
+        // separately-carried copy of `hash_count`.
+        let bucket_count = hasher.hash_count() / config.band_size;
+        LshIndex {
+            hasher,
+            frac_hasher: FracMinHash::new(config.frac_scale),
+            buckets: (0..bucket_count).map(|_| HashMap::new()).collect(),
+            frac_postings: HashMap::new(),
+            documents: HashMap::new(),
+            signatures: HashMap::new(),
+            frac_sketches: HashMap::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &LshIndexConfig {
+        &self.config
+    }
+
+    pub fn document(&self, id: usize) -> Option<&String> {
+        self.documents.get(&id)
+    }
+
+    /// Indexes `text` under `id`, first removing any existing document with that id so its stale
+    /// postings don't linger in `self.buckets` forever (nothing else stops a caller from reusing an
+    /// id to mean "update this document").
+    pub fn add_document(&mut self, id: usize, text: String) {
+        let shingles = document_shingles(&text, self.config.shingle_mode);
+        let signature = self.hasher.chunked_min_hash(&shingles, self.config.band_size);
+        self.insert_signature(id, text, &shingles, signature);
+    }
+
+    /// Batch equivalent of calling `add_document` in a loop: shingling and min-hashing each
+    /// document is independent of the others and of `self`, so that work is done with `par_iter`
+    /// before the (non-thread-safe) bucket/document maps are updated one document at a time.
+    pub fn add_documents(&mut self, documents: Vec<(usize, String)>) {
+        let hasher = &self.hasher;
+        let config = self.config;
+        let prepared: Vec<PreparedDocument> = documents
+            .into_par_iter()
+            .map(|(id, text)| {
+                let shingles = document_shingles(&text, config.shingle_mode);
+                let signature = hasher.chunked_min_hash(&shingles, config.band_size);
+                (id, text, shingles, signature)
+            })
+            .collect();
+        for (id, text, shingles, signature) in prepared {
+            self.insert_signature(id, text, &shingles, signature);
+        }
+    }
+
+    fn insert_signature(
+        &mut self,
+        id: usize,
+        text: String,
+        shingles: &HashSet<u64>,
+        signature: Signature,
+    ) {
+        if self.documents.contains_key(&id) {
+            self.remove_document(id);
+        }
+        // Postings are RoaringBitmaps, which store u32 ids, so every document id must fit in a
+        // u32; an id freed by remove_document can be used again.
+        assert!(
+            id <= u32::MAX as usize,
+            "LshIndex document ids must fit in a u32, got {id}"
+        );
+        let doc_id = id as u32;
+        for (bucket_index, band_hash) in &signature {
+            self.buckets[*bucket_index]
+                .entry(*band_hash)
+                .or_default()
+                .insert(doc_id);
+        }
+        let frac_sketch = self.frac_hasher.sketch(shingles);
+        for &hash in &frac_sketch {
+            self.frac_postings.entry(hash).or_default().insert(doc_id);
+        }
+        self.signatures.insert(id, signature);
+        self.frac_sketches.insert(id, frac_sketch);
+        self.documents.insert(id, text);
+    }
+
+    pub fn remove_document(&mut self, id: usize) {
+        let doc_id = id as u32;
+        if let Some(signature) = self.signatures.remove(&id) {
+            for (bucket_index, band_hash) in signature {
+                if let Some(posting) = self.buckets[bucket_index].get_mut(&band_hash) {
+                    posting.remove(doc_id);
+                    if posting.is_empty() {
+                        self.buckets[bucket_index].remove(&band_hash);
+                    }
+                }
+            }
+        }
+        if let Some(frac_sketch) = self.frac_sketches.remove(&id) {
+            for hash in frac_sketch {
+                if let Some(posting) = self.frac_postings.get_mut(&hash) {
+                    posting.remove(doc_id);
+                    if posting.is_empty() {
+                        self.frac_postings.remove(&hash);
+                    }
+                }
+            }
+        }
+        self.documents.remove(&id);
+    }
+
+    /// Returns every document id sharing at least one band with `query`, as the union of the
+    /// matching bands' posting bitmaps.
+    pub fn search(&self, query: &str) -> RoaringBitmap {
+        let mut matches = RoaringBitmap::new();
+        for posting in self.matching_postings(query) {
+            matches |= posting;
+        }
+        matches
+    }
+
+    /// Returns document ids that share at least `min_bands` bands with `query`, letting a caller
+    /// cut the refine set before the expensive exact-similarity pass. Tallying band membership
+    /// isn't a bitmap primitive, so this counts per-id hits directly; the union in `search` is
+    /// what stays a pure bitmap operation.
+    pub fn search_with_min_bands(&self, query: &str, min_bands: usize) -> RoaringBitmap {
+        let mut band_counts: HashMap<u32, usize> = HashMap::new();
+        for posting in self.matching_postings(query) {
+            for doc_id in posting.iter() {
+                *band_counts.entry(doc_id).or_insert(0) += 1;
+            }
+        }
+        band_counts
+            .into_iter()
+            .filter(|&(_, count)| count >= min_bands)
+            .map(|(doc_id, _)| doc_id)
+            .collect()
+    }
+
+    /// Candidate generation for containment queries: returns every document id whose FracMinHash
+    /// sketch shares at least one retained hash with `query`'s. Reusing the Jaccard-tuned
+    /// `buckets` here would require every row of a whole band to collide, which a short `query`
+    /// and a much longer containing document almost never do; a single shared sketch hash is
+    /// enough because containment only needs `query`'s retained hashes to be a subset of theirs.
+    pub fn search_containment(&self, query: &str) -> RoaringBitmap {
+        let shingles = document_shingles(query, self.config.shingle_mode);
+        let sketch = self.frac_hasher.sketch(&shingles);
+        let mut matches = RoaringBitmap::new();
+        for hash in sketch {
+            if let Some(posting) = self.frac_postings.get(&hash) {
+                matches |= posting;
+            }
+        }
+        matches
+    }
+
+    fn matching_postings(&self, query: &str) -> Vec<&RoaringBitmap> {
+        let shingles = document_shingles(query, self.config.shingle_mode);
+        let signature = self.hasher.chunked_min_hash(&shingles, self.config.band_size);
+        signature
+            .iter()
+            .filter_map(|(bucket_index, band_hash)| self.buckets[*bucket_index].get(band_hash))
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> bincode::Result<()> {
+        let file = File::create(path)?;
+        bincode::serialize_into(BufWriter::new(file), self)
+    }
+
+    pub fn load(path: &Path) -> bincode::Result<Self> {
+        let file = File::open(path)?;
+        bincode::deserialize_from(BufReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trips_an_index() {
+        let config = LshIndexConfig {
+            hash_count: 10,
+            band_size: 2,
+            shingle_mode: ShingleMode::Fixed,
+            frac_scale: 4,
+        };
+        let mut index = LshIndex::new(config);
+        index.add_document(0, "the quick brown fox".to_string());
+        index.add_document(1, "the quick brown fox jumps".to_string());
+
+        let path = std::env::temp_dir().join("lsh_rs_round_trip_test.bin");
+        index.save(&path).expect("save should succeed");
+        let loaded = LshIndex::load(&path).expect("load should succeed");
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.config().hash_count, config.hash_count);
+        assert_eq!(loaded.document(0), index.document(0));
+        assert_eq!(
+            loaded.search("the quick brown fox"),
+            index.search("the quick brown fox")
+        );
+    }
+
+    #[test]
+    fn search_with_min_bands_is_stricter_than_search() {
+        let config = LshIndexConfig {
+            hash_count: 10,
+            band_size: 2,
+            shingle_mode: ShingleMode::Fixed,
+            frac_scale: 4,
+        };
+        let mut index = LshIndex::new(config);
+        index.add_document(0, "the quick brown fox jumps over the lazy dog".to_string());
+        index.add_document(1, "completely unrelated text about something else".to_string());
+
+        let query = "the quick brown fox jumps over the lazy dog";
+        let loose_matches = index.search(query);
+        let strict_matches = index.search_with_min_bands(query, config.hash_count / config.band_size);
+
+        assert!(strict_matches.len() <= loose_matches.len());
+        assert!(strict_matches.contains(0));
+        assert!(!strict_matches.contains(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be a multiple of")]
+    fn new_rejects_a_band_size_that_does_not_divide_hash_count() {
+        LshIndex::new(LshIndexConfig {
+            hash_count: 10,
+            band_size: 3,
+            shingle_mode: ShingleMode::Fixed,
+            frac_scale: 4,
+        });
+    }
+
+    #[test]
+    fn search_containment_finds_a_short_snippet_of_a_much_longer_document() {
+        let config = LshIndexConfig {
+            hash_count: 20,
+            // Wide enough that a whole band colliding by chance on a random permutation is
+            // negligible, so the "banded search misses it" assertion below isn't flaky.
+            band_size: 10,
+            shingle_mode: ShingleMode::Fixed,
+            // Low enough that a 30-character snippet is all but certain to retain a hash shared
+            // with its source, without this test depending on specific hash values.
+            frac_scale: 2,
+        };
+        let mut index = LshIndex::new(config);
+        let source = "the quick brown fox jumps over the lazy dog while the sun sets slowly";
+        index.add_document(0, source.to_string());
+        index.add_document(1, "completely unrelated text about something else entirely".to_string());
+
+        // Far too short for banded search to find its own source document...
+        let snippet = &source[..30];
+        assert!(!index.search(snippet).contains(0));
+        // ...but containment search finds it via a single shared retained hash.
+        assert!(index.search_containment(snippet).contains(0));
+        assert!(!index.search_containment(snippet).contains(1));
+    }
+}
@@ -1,65 +1,39 @@
-use std::cmp::Reverse;
-use std::collections::hash_map::DefaultHasher;
-use std::collections::{BinaryHeap, HashMap, HashSet};
-use std::hash::{Hash, Hasher};
+use std::collections::HashSet;
 use std::time::Instant;
 
 use rand::prelude::ThreadRng;
 use rand::seq::SliceRandom;
 use rand::Rng;
 use rayon::prelude::*;
+use roaring::RoaringBitmap;
+
+mod banding;
+mod cdc;
+mod frac_minhash;
+mod index;
+mod minhash;
+mod shingles;
+
+use frac_minhash::FracMinHash;
+use index::{LshIndex, LshIndexConfig};
+use shingles::{document_shingles, ShingleMode};
 
 const HASH_COUNT: usize = 100;
 const BAND_SIZE: usize = 2;
 const SHINGLE_SIZE: usize = 4;
-
-fn chunked_min_hash(document: &str) -> Vec<(usize, u64)> {
-    // single hash function. for justification, see https://robertheaton.com/2014/05/02/jaccard-similarity-and-minhash-for-winners/
-    // and http://web.eecs.utk.edu/~jplank/plank/classes/cs494/494/notes/Min-Hash/index.html
-    let shingle_count = document.len() - SHINGLE_SIZE + 1;
-
-    let mut heap = BinaryHeap::with_capacity(shingle_count);
-
-    let mut hashes = vec![];
-    for idx in 0..shingle_count {
-        let shingle = &document[idx..idx + SHINGLE_SIZE];
-        let mut hasher = DefaultHasher::new();
-        shingle.hash(&mut hasher);
-        let shingle_hash = hasher.finish();
-        heap.push(Reverse(shingle_hash));
-    }
-
-    for _ in 0..HASH_COUNT {
-        // try to gracefully handle shingle_count < HASH_COUNT situation. it should still work,
-        // at least under certain conditions
-        if heap.is_empty() {
-            break;
-        }
-        hashes.push(heap.pop().unwrap().0);
-    }
-
-    hashes
-        .chunks(BAND_SIZE)
-        .map(|chunk| {
-            let mut hasher = DefaultHasher::new();
-            chunk.hash(&mut hasher);
-            hasher.finish()
-        })
-        .enumerate()
-        .collect()
-}
-
-fn string_shingles(document: &str) -> HashSet<u64> {
-    let shingle_count = document.len() - SHINGLE_SIZE;
-    let mut shingles = HashSet::new();
-    for idx in 0..shingle_count {
-        let shingle = &document[idx..idx + SHINGLE_SIZE];
-        let mut hasher = DefaultHasher::new();
-        shingle.hash(&mut hasher);
-        let shingle_hash = hasher.finish();
-        shingles.insert(shingle_hash);
-    }
-    shingles
+// 1/FRAC_MIN_HASH_SCALE of shingle hashes are retained by a FracMinHash sketch.
+const FRAC_MIN_HASH_SCALE: u64 = 16;
+// Retrieve pairs whose Jaccard similarity is above roughly this threshold.
+const TARGET_SIMILARITY_THRESHOLD: f32 = 0.8;
+
+/// Which similarity statistic `nearest_neighbors` should rank candidates by.
+#[derive(Clone, Copy)]
+enum RankBy {
+    /// Exact Jaccard similarity over the full shingle sets.
+    Jaccard,
+    /// Estimated containment of the query within the candidate, via FracMinHash sketches. Use
+    /// this to find documents the query is a subset of, even when they are much longer.
+    Containment,
 }
 
 fn jaccard_similarity(a: &HashSet<u64>, b: &HashSet<u64>) -> f32 {
@@ -70,16 +44,27 @@ fn jaccard_similarity(a: &HashSet<u64>, b: &HashSet<u64>) -> f32 {
 fn nearest_neighbors(
     query: &str,
     n: usize,
-    matches: &HashSet<usize>,
-    documents: &[String],
+    matches: &RoaringBitmap,
+    index: &LshIndex,
+    shingle_mode: ShingleMode,
+    rank_by: RankBy,
+    frac_hasher: &FracMinHash,
 ) -> Vec<(usize, f32)> {
-    let query_shingles = string_shingles(query);
-    let mut similar_matches: Vec<(usize, f32)> = matches
+    let query_shingles = document_shingles(query, shingle_mode);
+    let query_frac_sketch = frac_hasher.sketch(&query_shingles);
+    let match_ids: Vec<usize> = matches.iter().map(|id| id as usize).collect();
+    let mut similar_matches: Vec<(usize, f32)> = match_ids
         .par_iter()
         .map(|m| {
-            let document = &documents[*m];
-            let match_shingles = string_shingles(document);
-            let similarity = jaccard_similarity(&query_shingles, &match_shingles);
+            let document = index.document(*m).expect("matched id must be indexed");
+            let match_shingles = document_shingles(document, shingle_mode);
+            let similarity = match rank_by {
+                RankBy::Jaccard => jaccard_similarity(&query_shingles, &match_shingles),
+                RankBy::Containment => {
+                    let match_frac_sketch = frac_hasher.sketch(&match_shingles);
+                    frac_minhash::containment_estimate(&query_frac_sketch, &match_frac_sketch)
+                }
+            };
             (*m, similarity)
         })
         .collect();
@@ -90,50 +75,6 @@ fn nearest_neighbors(
     similar_matches
 }
 
-fn index_documents(documents: &mut Vec<String>) -> Vec<HashMap<u64, Vec<usize>>> {
-    let mut buckets: Vec<HashMap<u64, Vec<usize>>> = vec![];
-
-    let bucket_count = HASH_COUNT / BAND_SIZE;
-    for _ in 0..bucket_count {
-        buckets.push(HashMap::new());
-    }
-
-    let chunked_min_hashes: Vec<Vec<(usize, u64)>> = documents
-        .par_iter()
-        .map(|document| chunked_min_hash(document))
-        .collect();
-
-    for (document_index, chunked_min_hash) in chunked_min_hashes.iter().enumerate() {
-        for (bucket_index, min_hash) in chunked_min_hash.iter() {
-            let bucket = &mut buckets[*bucket_index];
-            bucket
-                .entry(*min_hash)
-                .or_insert(vec![])
-                .push(document_index);
-        }
-    }
-    buckets
-}
-
-fn search_index(
-    documents: &[String],
-    buckets: &mut [HashMap<u64, Vec<usize>>],
-    query: &str,
-    n: usize,
-) -> (HashSet<usize>, Vec<(usize, f32)>) {
-    let mut matches: HashSet<usize> = HashSet::new();
-    let query_signature = chunked_min_hash(query);
-    for (bucket_index, min_hash) in query_signature.iter() {
-        let bucket = &mut buckets[*bucket_index];
-        if bucket.contains_key(min_hash) {
-            matches.extend(&bucket[min_hash]);
-        }
-    }
-
-    let top_neighbors = nearest_neighbors(query, n, &matches, documents);
-    (matches, top_neighbors)
-}
-
 // constants for synthetic data
 const ORIGINAL_DOCUMENT_COUNT: usize = 10000;
 const PER_DOCUMENT_MUTATION_COUNT: usize = 9; // 10000 + 9*10000 = 100000 total documents
@@ -175,7 +116,6 @@ fn generate_random_string(rng: &mut ThreadRng, random_string: &str) -> String {
 }
 
 fn main() {
-    assert_eq!(HASH_COUNT % BAND_SIZE, 0);
     let mut rng = rand::thread_rng();
 
     let mut documents = vec![];
@@ -198,16 +138,54 @@ fn main() {
         "Generation of {} documents done, starting indexing...\n",
         &documents.len()
     );
+    let frac_hasher = FracMinHash::new(FRAC_MIN_HASH_SCALE);
+    let config = LshIndexConfig::for_threshold(
+        HASH_COUNT,
+        TARGET_SIMILARITY_THRESHOLD,
+        ShingleMode::Fixed,
+        FRAC_MIN_HASH_SCALE,
+    );
+    let shingle_mode = config.shingle_mode;
+    let mut index = LshIndex::new(config);
     let indexing_start = Instant::now();
 
-    let mut buckets = index_documents(&mut documents);
+    let to_index: Vec<(usize, String)> = documents.iter().cloned().enumerate().collect();
+    index.add_documents(to_index);
     let indexing_duration = indexing_start.elapsed();
     println!("Done indexing in {:?}, searching", indexing_duration);
 
+    // Indexing in bulk doesn't preclude incremental updates: add one more document, then remove
+    // it again, exercising both `add_document` and `remove_document` directly.
+    let extra_id = documents.len();
+    let extra_document: String = (0..DOCUMENT_LEN).map(|_| random_char(&mut rng)).collect();
+    index.add_document(extra_id, extra_document);
+    index.remove_document(extra_id);
+
+    // Round-trip the index through a file with `save` and `load`; the file is deleted afterwards,
+    // so the next run indexes the corpus again.
+    let index_path = std::env::temp_dir().join("lsh_rs_index.bin");
+    index.save(&index_path).expect("failed to save index");
+    let index = LshIndex::load(&index_path).expect("failed to load index");
+    std::fs::remove_file(&index_path).ok();
+    println!(
+        "Reloaded index built with hash_count {} and band_size {}",
+        index.config().hash_count,
+        index.config().band_size
+    );
+
     let search_start = Instant::now();
     let query = &documents[0];
 
-    let (matches, top_neighbors) = search_index(&documents, &mut buckets, query, 25);
+    let matches = index.search(query);
+    let top_neighbors = nearest_neighbors(
+        query,
+        25,
+        &matches,
+        &index,
+        shingle_mode,
+        RankBy::Jaccard,
+        &frac_hasher,
+    );
     let search_duration = search_start.elapsed();
 
     println!(
@@ -216,6 +194,18 @@ fn main() {
         &documents[0],
         &matches.len()
     );
+
+    // Requiring more than one band hit trims the candidate set before the expensive exact
+    // similarity pass, at the cost of only keeping closer matches.
+    let strict_bands = (config.hash_count / config.band_size).max(2) / 2;
+    let strict_matches = index.search_with_min_bands(query, strict_bands);
+    println!(
+        "Requiring at least {} band hits narrows the {} matches above down to {}",
+        strict_bands,
+        &matches.len(),
+        strict_matches.len()
+    );
+
     for (match_idx, (idx, similarity)) in top_neighbors.iter().enumerate() {
         println!(
             "Match {}: {}, similarity {}, index {}",
@@ -225,4 +215,40 @@ fn main() {
             idx
         );
     }
+
+    // A short substring scores low on Jaccard against the much longer documents it came from, but
+    // high on containment, which is the statistic FracMinHash exists to estimate.
+    let snippet = &documents[0][..DOCUMENT_LEN / 10];
+    let snippet_shingles = document_shingles(snippet, shingle_mode);
+    let snippet_sketch = frac_hasher.sketch(&snippet_shingles);
+    let original_shingles = document_shingles(&documents[0], shingle_mode);
+    let original_sketch = frac_hasher.sketch(&original_shingles);
+    println!(
+        "\nSnippet vs. its source document: jaccard {}, containment {}",
+        frac_minhash::jaccard_estimate(&snippet_sketch, &original_sketch),
+        frac_minhash::containment_estimate(&snippet_sketch, &original_sketch)
+    );
+
+    // Banded MinHash search needs a whole band of rows to collide, which a snippet this much
+    // shorter than its source essentially never manages; containment search instead looks
+    // candidates up by shared FracMinHash sketch hash, which only needs one.
+    let snippet_matches = index.search_containment(snippet);
+    let snippet_neighbors = nearest_neighbors(
+        snippet,
+        5,
+        &snippet_matches,
+        &index,
+        shingle_mode,
+        RankBy::Containment,
+        &frac_hasher,
+    );
+    println!("\nContainment matches for a snippet of document 0:");
+    for (match_idx, (idx, containment)) in snippet_neighbors.iter().enumerate() {
+        println!(
+            "Match {}: index {}, containment {}",
+            match_idx + 1,
+            idx,
+            containment
+        );
+    }
 }
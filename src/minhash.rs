@@ -0,0 +1,91 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// 2^61 - 1, a Mersenne prime. It is smaller than 2^64, so 64-bit shingle hashes are reduced mod p
+// by the permutation.
+const MERSENNE_PRIME_61: u64 = (1 << 61) - 1;
+
+fn affine_permutation(a: u64, b: u64, x: u64) -> u64 {
+    let a = a as u128;
+    let b = b as u128;
+    let x = x as u128;
+    let p = MERSENNE_PRIME_61 as u128;
+    ((a * x + b) % p) as u64
+}
+
+// k-permutation MinHash: h_i(x) = (a_i * x + b_i) mod p. Coefficients are drawn once and reused
+// for every document and query, since signatures are only comparable under the same permutations.
+#[derive(Serialize, Deserialize)]
+pub struct MinHasher {
+    coefficients: Vec<(u64, u64)>,
+}
+
+impl MinHasher {
+    pub fn new(hash_count: usize) -> Self {
+        let mut rng = rand::thread_rng();
+        let coefficients = (0..hash_count)
+            .map(|_| {
+                // a is odd and below p, so it is nonzero mod p and h_i is a permutation mod p.
+                let a = rng.gen_range(1..MERSENNE_PRIME_61 - 1) | 1;
+                let b = rng.gen_range(0..MERSENNE_PRIME_61);
+                (a, b)
+            })
+            .collect();
+        MinHasher { coefficients }
+    }
+
+    pub fn hash_count(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    // Computes the MinHash signature over `shingles`, then bands it into hash_count() / band_size
+    // buckets, each keyed by the hash of its band_size contiguous signature entries.
+    pub fn chunked_min_hash(&self, shingles: &HashSet<u64>, band_size: usize) -> Vec<(usize, u64)> {
+        let signature: Vec<u64> = self
+            .coefficients
+            .iter()
+            .map(|(a, b)| {
+                shingles
+                    .iter()
+                    .map(|&x| affine_permutation(*a, *b, x))
+                    .min()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        signature
+            .chunks(band_size)
+            .map(|chunk| {
+                let mut hasher = DefaultHasher::new();
+                chunk.hash(&mut hasher);
+                hasher.finish()
+            })
+            .enumerate()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_count_matches_constructor_argument() {
+        let hasher = MinHasher::new(42);
+        assert_eq!(hasher.hash_count(), 42);
+    }
+
+    #[test]
+    fn chunked_min_hash_is_deterministic_for_the_same_hasher() {
+        let hasher = MinHasher::new(20);
+        let shingles: HashSet<u64> = [1, 2, 3, 4, 5].into_iter().collect();
+        assert_eq!(
+            hasher.chunked_min_hash(&shingles, 2),
+            hasher.chunked_min_hash(&shingles, 2)
+        );
+    }
+}
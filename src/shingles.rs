@@ -0,0 +1,38 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cdc;
+use crate::SHINGLE_SIZE;
+
+/// Which shingling scheme turns a document into the hash set fed to Jaccard/MinHash.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum ShingleMode {
+    /// Fixed-width, fixed-offset `SHINGLE_SIZE`-char windows.
+    Fixed,
+    /// Variable-length, content-defined chunks from `cdc::cdc_shingles`, robust to the
+    /// insertions/deletions the synthetic benchmark mutates documents with.
+    ContentDefined,
+}
+
+pub fn document_shingles(document: &str, mode: ShingleMode) -> HashSet<u64> {
+    match mode {
+        ShingleMode::Fixed => string_shingles(document),
+        ShingleMode::ContentDefined => cdc::cdc_shingles(document),
+    }
+}
+
+pub fn string_shingles(document: &str) -> HashSet<u64> {
+    let shingle_count = document.len() - SHINGLE_SIZE;
+    let mut shingles = HashSet::new();
+    for idx in 0..shingle_count {
+        let shingle = &document[idx..idx + SHINGLE_SIZE];
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let shingle_hash = hasher.finish();
+        shingles.insert(shingle_hash);
+    }
+    shingles
+}